@@ -0,0 +1,284 @@
+//! Batch duplicate detection backed by a [BK-tree](https://en.wikipedia.org/wiki/BK-tree)
+//!
+//! A BK-tree indexes hashes under the Hamming metric so that "find everything
+//! within distance `d`" queries prune the search space using the triangle
+//! inequality rather than scanning every hash.  This makes it practical to find
+//! near-duplicate images across a large directory.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::{hash_image_sized, Hash, HashAlg};
+
+/// A node in the BK-tree, keyed on a [`Hash`] with child edges labelled by the
+/// Hamming distance to the parent.
+struct Node<T> {
+    hash: Hash,
+    value: T,
+    children: BTreeMap<u32, Node<T>>,
+}
+
+/// A BK-tree mapping hashes to values of type `T`
+///
+/// Values are inserted with [`BkTree::insert`] and looked up by Hamming radius
+/// with [`BkTree::find`].
+pub struct BkTree<T> {
+    root: Option<Node<T>>,
+}
+
+impl<T> Default for BkTree<T> {
+    fn default() -> BkTree<T> {
+        BkTree { root: None }
+    }
+}
+
+impl<T> BkTree<T> {
+    /// Creates an empty tree
+    pub fn new() -> BkTree<T> {
+        BkTree::default()
+    }
+
+    /// Inserts a hash and its associated value into the tree
+    ///
+    /// Walks from the root following the child edge labelled by the distance to
+    /// the current node, attaching a new child at that label when none exists.
+    pub fn insert(&mut self, hash: Hash, value: T) {
+        match self.root {
+            None => {
+                self.root = Some(Node {
+                    hash,
+                    value,
+                    children: BTreeMap::new(),
+                });
+            }
+            Some(ref mut root) => {
+                let mut node = root;
+
+                loop {
+                    let distance = node.hash.hamming_distance(&hash);
+
+                    if let std::collections::btree_map::Entry::Vacant(entry) =
+                        node.children.entry(distance)
+                    {
+                        entry.insert(Node {
+                            hash,
+                            value,
+                            children: BTreeMap::new(),
+                        });
+                        break;
+                    }
+
+                    node = node.children.get_mut(&distance).unwrap();
+                }
+            }
+        }
+    }
+
+    /// Returns every value whose hash lies within `radius` of `target`
+    ///
+    /// Each match is returned alongside its Hamming distance.  Children are only
+    /// visited when their edge label lies in `[d - radius, d + radius]`, pruning
+    /// subtrees that cannot contain a match.
+    ///
+    /// # Example
+    /// ```
+    /// # use dhash::Hash;
+    /// # use dhash::bktree::BkTree;
+    /// let hex = ["00", "01", "03", "07", "0f", "1f", "3f", "ff", "f0", "aa"];
+    /// let hashes: Vec<Hash> = hex.iter().map(|h| Hash::from_hex(h).unwrap()).collect();
+    ///
+    /// let mut tree = BkTree::new();
+    /// for (index, hash) in hashes.iter().enumerate() {
+    ///     tree.insert(hash.clone(), index);
+    /// }
+    ///
+    /// // The pruned BK-tree query must agree with a brute-force linear scan at
+    /// // every radius.
+    /// for &radius in &[0u32, 1, 2, 3, 4, 8] {
+    ///     for target in &hashes {
+    ///         let mut got: Vec<usize> =
+    ///             tree.find(target, radius).into_iter().map(|(index, _)| *index).collect();
+    ///         got.sort();
+    ///
+    ///         let mut expected: Vec<usize> = hashes
+    ///             .iter()
+    ///             .enumerate()
+    ///             .filter(|(_, hash)| target.hamming_distance(hash) <= radius)
+    ///             .map(|(index, _)| index)
+    ///             .collect();
+    ///         expected.sort();
+    ///
+    ///         assert_eq!(got, expected);
+    ///     }
+    /// }
+    /// ```
+    pub fn find(&self, target: &Hash, radius: u32) -> Vec<(&T, u32)> {
+        let mut matches = Vec::new();
+
+        if let Some(ref root) = self.root {
+            let mut stack = vec![root];
+
+            while let Some(node) = stack.pop() {
+                let distance = node.hash.hamming_distance(target);
+
+                if distance <= radius {
+                    matches.push((&node.value, distance));
+                }
+
+                let low = distance.saturating_sub(radius);
+                let high = distance + radius;
+
+                for (_label, child) in node.children.range(low..=high) {
+                    stack.push(child);
+                }
+            }
+        }
+
+        matches
+    }
+}
+
+/// Computes the hash for each path, skipping any image that fails to decode
+///
+/// When the `cache` feature is enabled and `cache_dir` is `Some`, hashes are
+/// read from and written to the on-disk cache transparently.
+fn hash_paths(paths: &[PathBuf], alg: HashAlg, cache_dir: Option<&Path>) -> Vec<(PathBuf, Hash)> {
+    #[cfg(feature = "cache")]
+    let cache = cache_dir.and_then(|dir| match crate::cache::HashCache::new(dir) {
+        Ok(cache) => Some(cache),
+        Err(err) => {
+            log::warn!("disabling cache at {}: {}", dir.display(), err);
+            None
+        }
+    });
+
+    #[cfg(not(feature = "cache"))]
+    let _ = cache_dir;
+
+    let mut hashes = Vec::with_capacity(paths.len());
+
+    for path in paths {
+        #[cfg(feature = "cache")]
+        if let Some(cache) = &cache {
+            match cache.get_or_compute(path, alg, 8) {
+                Ok(hash) => hashes.push((path.clone(), hash)),
+                Err(err) => log::warn!("skipping {}: {}", path.display(), err),
+            }
+            continue;
+        }
+
+        match image::open(path) {
+            Ok(img) => hashes.push((path.clone(), hash_image_sized(&img, alg, 8))),
+            Err(err) => log::warn!("skipping {}: {}", path.display(), err),
+        }
+    }
+
+    hashes
+}
+
+/// Groups the given image paths into clusters of near-duplicates
+///
+/// Clusters are built greedily in input order: each image not yet assigned to
+/// a cluster starts a new one holding every unassigned image whose hash is
+/// within `threshold` Hamming distance of its own.  An image that is already
+/// assigned is not considered again, so two images within `threshold` of each
+/// other can end up in different clusters.  An image with no unassigned image
+/// within `threshold` is returned as a singleton cluster.
+///
+/// # Example
+/// ```
+/// # use dhash::bktree::cluster_duplicates;
+/// # use dhash::HashAlg;
+/// use image::{ImageBuffer, Luma};
+/// use std::fs;
+///
+/// let dir = std::env::temp_dir().join(format!("dhash-cluster-{}", std::process::id()));
+/// fs::create_dir_all(&dir).unwrap();
+///
+/// let a = dir.join("a.png");
+/// let b = dir.join("b.png");
+/// let c = dir.join("c.png");
+///
+/// // `a` and `b` are identical; `c` is a flat image with a very different hash.
+/// ImageBuffer::from_fn(64, 64, |x, _| Luma([(255 - x * 4) as u8])).save(&a).unwrap();
+/// ImageBuffer::from_fn(64, 64, |x, _| Luma([(255 - x * 4) as u8])).save(&b).unwrap();
+/// ImageBuffer::from_fn(64, 64, |_, _| Luma([0u8])).save(&c).unwrap();
+///
+/// let paths = vec![a.clone(), b.clone(), c.clone()];
+/// let clusters = cluster_duplicates(&paths, HashAlg::Gradient, 5, None);
+///
+/// let pair = clusters.iter().find(|group| group.contains(&a)).unwrap();
+/// assert!(pair.contains(&b));
+/// assert_eq!(pair.len(), 2);
+/// assert!(clusters.iter().any(|group| group == &vec![c.clone()]));
+///
+/// fs::remove_dir_all(&dir).ok();
+/// ```
+pub fn cluster_duplicates(
+    paths: &[PathBuf],
+    alg: HashAlg,
+    threshold: u32,
+    cache_dir: Option<&Path>,
+) -> Vec<Vec<PathBuf>> {
+    let hashes = hash_paths(paths, alg, cache_dir);
+
+    let mut tree = BkTree::new();
+
+    for (index, (_, hash)) in hashes.iter().enumerate() {
+        tree.insert(hash.clone(), index);
+    }
+
+    let mut assigned = vec![false; hashes.len()];
+    let mut clusters = Vec::new();
+
+    for (index, (_, hash)) in hashes.iter().enumerate() {
+        if assigned[index] {
+            continue;
+        }
+
+        let mut cluster = Vec::new();
+
+        for (member, _) in tree.find(hash, threshold) {
+            if !assigned[*member] {
+                assigned[*member] = true;
+                cluster.push(hashes[*member].0.clone());
+            }
+        }
+
+        clusters.push(cluster);
+    }
+
+    clusters
+}
+
+/// Lists every regular file in a directory, non-recursively, sorted by path
+///
+/// No filtering by file type is done; files that are not images are skipped
+/// later when they fail to decode.
+fn image_paths(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_file() {
+            paths.push(path);
+        }
+    }
+
+    paths.sort();
+
+    Ok(paths)
+}
+
+/// Scans a directory and returns the clusters of near-duplicate images within it
+pub fn scan_directory(
+    dir: &Path,
+    alg: HashAlg,
+    threshold: u32,
+    cache_dir: Option<&Path>,
+) -> std::io::Result<Vec<Vec<PathBuf>>> {
+    let paths = image_paths(dir)?;
+
+    Ok(cluster_duplicates(&paths, alg, threshold, cache_dir))
+}
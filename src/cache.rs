@@ -0,0 +1,100 @@
+//! An optional on-disk cache of computed hashes, keyed by file content digest
+//!
+//! Decoding images is by far the most expensive part of hashing a directory, so
+//! this cache stores the perceptual hash for a file keyed on a fast content
+//! digest of its raw bytes together with the algorithm and size parameters.  A
+//! subsequent run with the same parameters returns the stored hash without ever
+//! decoding the image again.
+//!
+//! This module is only compiled when the `cache` feature is enabled.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{hash_image_sized, Hash, HashAlg};
+
+/// A directory-backed cache of computed hashes
+pub struct HashCache {
+    dir: PathBuf,
+}
+
+impl HashCache {
+    /// Opens (creating if necessary) a cache rooted at the given directory
+    pub fn new<P: AsRef<Path>>(dir: P) -> io::Result<HashCache> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        Ok(HashCache { dir })
+    }
+
+    /// Cache file path for a given content digest and hash parameters
+    fn entry_path(&self, digest: &str, alg: HashAlg, scale: u32) -> PathBuf {
+        let suffix = match alg {
+            HashAlg::Gradient => "gradient",
+            HashAlg::Mean => "mean",
+            HashAlg::Dct => "dct",
+        };
+
+        self.dir.join(format!("{}-{}-{}", digest, suffix, scale))
+    }
+
+    /// Returns the hash for `path`, computing and caching it on a miss
+    ///
+    /// On a cache hit the image is never decoded; only its raw bytes are read
+    /// and digested.
+    ///
+    /// # Example
+    /// ```
+    /// # use dhash::cache::HashCache;
+    /// # use dhash::HashAlg;
+    /// use image::{ImageBuffer, Luma};
+    /// use std::fs;
+    ///
+    /// let root = std::env::temp_dir().join(format!("dhash-cache-{}", std::process::id()));
+    /// let img_path = root.join("img.png");
+    /// fs::create_dir_all(&root).unwrap();
+    /// ImageBuffer::from_fn(64, 64, |x, y| Luma([((x + y) % 256) as u8]))
+    ///     .save(&img_path)
+    ///     .unwrap();
+    ///
+    /// let cache = HashCache::new(root.join("cache")).unwrap();
+    /// let first = cache.get_or_compute(&img_path, HashAlg::Gradient, 8).unwrap();
+    /// // The second call is served from disk and must round-trip to the same hash.
+    /// let second = cache.get_or_compute(&img_path, HashAlg::Gradient, 8).unwrap();
+    /// assert_eq!(first, second);
+    ///
+    /// fs::remove_dir_all(&root).ok();
+    /// ```
+    pub fn get_or_compute(
+        &self,
+        path: &Path,
+        alg: HashAlg,
+        scale: u32,
+    ) -> io::Result<Hash> {
+        let bytes = fs::read(path)?;
+        let digest = blake3::hash(&bytes).to_hex();
+
+        let entry = self.entry_path(digest.as_str(), alg, scale);
+
+        if let Ok(contents) = fs::read_to_string(&entry) {
+            if let Ok(mut hash) = Hash::from_base64(contents.trim()) {
+                // base64 stores whole bytes, so restore the original bit count
+                let bits = (scale * scale) as usize;
+
+                if hash.bytes.len() == bits.div_ceil(8) {
+                    hash.bits = bits;
+                    return Ok(hash);
+                }
+            }
+        }
+
+        let img = image::load_from_memory(&bytes)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        let hash = hash_image_sized(&img, alg, scale);
+
+        fs::write(&entry, hash.to_base64())?;
+
+        Ok(hash)
+    }
+}
@@ -45,34 +45,230 @@ use image::imageops::{grayscale, resize, FilterType};
 use image::{GenericImageView, ImageBuffer};
 use image::{Luma, Pixel};
 
+pub mod bktree;
+
+#[cfg(feature = "cache")]
+pub mod cache;
+
 const IMG_SCALE: u32 = 8;
 
-/// Computes the `dhash` value of a given image
+/// The perceptual hash algorithm used to produce a signature
+///
+/// All three variants emit a 64 bit signature from an 8x8 grid of samples and
+/// can be compared against one another with [`hamming_distance`].
+///
+/// * `Gradient` is the classic `dhash` (difference hash) that compares the
+///   gradient between horizontally adjacent pixels
+/// * `Mean` is the `aHash` (average hash) that compares each pixel against the
+///   mean luma of the image
+/// * `Dct` is the `pHash` (perceptual hash) built from the low-frequency terms
+///   of the 2-D Discrete Cosine Transform
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlg {
+    /// The gradient (difference) hash, see [`get_dhash`]
+    Gradient,
+    /// The mean (average) hash
+    Mean,
+    /// The DCT based perceptual hash
+    ///
+    /// # Example
+    /// ```
+    /// # use dhash::{hash_image, hamming_distance, HashAlg};
+    /// use image::{ImageBuffer, Luma};
+    /// // The same content hashed twice produces an identical signature.
+    /// let img1 = ImageBuffer::from_fn(64, 64, |x, y| Luma([((x * 3 + y) % 256) as u8]));
+    /// let img2 = ImageBuffer::from_fn(64, 64, |x, y| Luma([((x * 3 + y) % 256) as u8]));
+    /// assert_eq!(
+    ///     hamming_distance(hash_image(&img1, HashAlg::Dct), hash_image(&img2, HashAlg::Dct)),
+    ///     0,
+    /// );
+    /// // A flat image is an exact match with itself, and structured content
+    /// // hashes differently from it.
+    /// let flat = ImageBuffer::from_fn(64, 64, |_, _| Luma([128u8]));
+    /// assert_eq!(
+    ///     hamming_distance(hash_image(&flat, HashAlg::Dct), hash_image(&flat, HashAlg::Dct)),
+    ///     0,
+    /// );
+    /// assert!(
+    ///     hamming_distance(hash_image(&img1, HashAlg::Dct), hash_image(&flat, HashAlg::Dct)) > 0,
+    /// );
+    /// ```
+    Dct,
+}
+
+/// Computes a perceptual hash of the given image using the chosen [`HashAlg`]
+///
+/// The result is a 64 bit signature that can be compared to other hashes of the
+/// same algorithm with [`hamming_distance`].
 ///
-/// A `dhash` is a signature of an image that can be compared to other images
-/// 
-/// Requires the `image` crate for loading in the image
-/// 
 /// # Example
-/// 
+///
 /// ```no_run
-/// # use dhash::get_dhash;
+/// # use dhash::{hash_image, HashAlg};
 /// # fn main() {
 /// let img = image::open("test.jpg").expect("Could not open image");
-/// let dhash = get_dhash(&img);
+/// let hash = hash_image(&img, HashAlg::Mean);
 /// # }
 /// ```
-/// 
-pub fn get_dhash<I: GenericImageView + 'static>(img: &I) -> u64 {
-    let buffered_image = to_grey_signature_image(img);
+pub fn hash_image<I: GenericImageView + 'static>(img: &I, alg: HashAlg) -> u64
+where
+    <<I as GenericImageView>::Pixel as Pixel>::Subpixel: Into<f64>,
+{
+    bits_to_u64(&hash_bits(img, alg, IMG_SCALE))
+}
 
-    let mut bits: [bool; (IMG_SCALE * IMG_SCALE) as usize] =
-        [false; (IMG_SCALE * IMG_SCALE) as usize];
+/// Computes a perceptual hash of the given image at an arbitrary scale
+///
+/// The `scale` controls the side length of the sampling grid, so a scale of 8
+/// produces a 64 bit hash (the default) and a scale of 16 produces a 256 bit
+/// hash for finer discrimination across large collections.  The returned
+/// [`Hash`] can be compared with [`Hash::hamming_distance`] regardless of
+/// length.
+///
+/// # Example
+///
+/// ```no_run
+/// # use dhash::{hash_image_sized, HashAlg};
+/// # fn main() {
+/// let img = image::open("test.jpg").expect("Could not open image");
+/// let hash = hash_image_sized(&img, HashAlg::Gradient, 16);
+/// # }
+/// ```
+pub fn hash_image_sized<I: GenericImageView + 'static>(
+    img: &I,
+    alg: HashAlg,
+    scale: u32,
+) -> Hash
+where
+    <<I as GenericImageView>::Pixel as Pixel>::Subpixel: Into<f64>,
+{
+    Hash::from_bits(&hash_bits(img, alg, scale))
+}
+
+fn hash_bits<I: GenericImageView + 'static>(img: &I, alg: HashAlg, scale: u32) -> Vec<bool>
+where
+    <<I as GenericImageView>::Pixel as Pixel>::Subpixel: Into<f64>,
+{
+    match alg {
+        HashAlg::Gradient => gradient_bits(img, scale),
+        HashAlg::Mean => mean_bits(img, scale),
+        HashAlg::Dct => dct_bits(img, scale),
+    }
+}
+
+/// Applies a naive 1-D DCT-II over a slice, returning the transformed coefficients
+///
+/// `X_k = sum_{n=0}^{N-1} x_n * cos(pi/N * (n + 0.5) * k)`
+fn dct_1d(input: &[f64]) -> Vec<f64> {
+    let n = input.len();
+    let mut output = vec![0.0; n];
+
+    for (k, out) in output.iter_mut().enumerate() {
+        let mut sum = 0.0;
+
+        for (i, value) in input.iter().enumerate() {
+            sum += value * (std::f64::consts::PI / n as f64 * (i as f64 + 0.5) * k as f64).cos();
+        }
+
+        *out = sum;
+    }
+
+    output
+}
+
+fn dct_bits<I: GenericImageView + 'static>(img: &I, scale: u32) -> Vec<bool>
+where
+    <<I as GenericImageView>::Pixel as Pixel>::Subpixel: Into<f64>,
+{
+    // The DCT is run over a grid four times the low-frequency block so the
+    // default scale of 8 transforms a 32x32 image and keeps the top-left 8x8.
+    let low_freq = scale as usize;
+    let dct_size = low_freq * 4;
+
+    let grey_image = grayscale(img);
+
+    let signature_image = resize(
+        &grey_image,
+        dct_size as u32,
+        dct_size as u32,
+        FilterType::Nearest,
+    );
+
+    let mut matrix = vec![vec![0.0f64; dct_size]; dct_size];
+
+    for (x, y, pixel) in signature_image.enumerate_pixels() {
+        matrix[y as usize][x as usize] = pixel[0].into();
+    }
+
+    // DCT across every row
+    for row in matrix.iter_mut() {
+        let transformed = dct_1d(row);
+        row.copy_from_slice(&transformed);
+    }
+
+    // DCT across every column.  The transpose access means the column index has
+    // to drive the loop, so the range loop is intentional here.
+    #[allow(clippy::needless_range_loop)]
+    for x in 0..dct_size {
+        let column: Vec<f64> = (0..dct_size).map(|y| matrix[y][x]).collect();
+        let transformed = dct_1d(&column);
+
+        for (y, row) in matrix.iter_mut().enumerate() {
+            row[x] = transformed[y];
+        }
+    }
+
+    // Take the top-left low frequency sub-block and average it excluding the
+    // DC (0,0) coefficient.
+    let mut sum = 0.0;
+
+    for (y, row) in matrix.iter().take(low_freq).enumerate() {
+        for (x, coeff) in row.iter().take(low_freq).enumerate() {
+            if x == 0 && y == 0 {
+                continue;
+            }
+            sum += coeff;
+        }
+    }
+
+    let mean = sum / (low_freq * low_freq - 1) as f64;
+
+    let mut bits = vec![false; low_freq * low_freq];
 
     let mut cur_value = 0;
 
-    for i in 0..IMG_SCALE {
-        for j in 0..IMG_SCALE {
+    for row in matrix.iter().take(low_freq) {
+        for coeff in row.iter().take(low_freq) {
+            bits[cur_value] = *coeff > mean;
+
+            cur_value += 1;
+        }
+    }
+
+    bits
+}
+
+fn bits_to_u64(bits: &[bool]) -> u64 {
+    let mut value = 0;
+
+    for (i, bit) in bits.iter().enumerate() {
+        if *bit {
+            value += 1 << i;
+        }
+    }
+
+    value
+}
+
+fn gradient_bits<I: GenericImageView + 'static>(img: &I, scale: u32) -> Vec<bool> {
+    let buffered_image = grey_signature_sized(img, scale);
+
+    let mut bits = vec![false; (scale * scale) as usize];
+
+    let mut cur_value = 0;
+
+    for i in 0..scale {
+        for j in 0..scale {
             let left_pixel = buffered_image.get_pixel(i, j);
             let right_pixel = buffered_image.get_pixel(i + 1, j);
 
@@ -82,17 +278,69 @@ pub fn get_dhash<I: GenericImageView + 'static>(img: &I) -> u64 {
         }
     }
 
-    let mut value = 0;
+    bits
+}
 
-    for i in 0..bits.len() {
-        if bits[i] {
-            value += 1 << i;
+fn mean_bits<I: GenericImageView + 'static>(img: &I, scale: u32) -> Vec<bool>
+where
+    <<I as GenericImageView>::Pixel as Pixel>::Subpixel: Into<f64>,
+{
+    let grey_image = grayscale(img);
+
+    let signature_image = resize(&grey_image, scale, scale, FilterType::Nearest);
+
+    let mut sum: f64 = 0.0;
+
+    for (_, _, pixel) in signature_image.enumerate_pixels() {
+        sum += pixel[0].into();
+    }
+
+    let mean = sum / f64::from(scale * scale);
+
+    let mut bits = vec![false; (scale * scale) as usize];
+
+    let mut cur_value = 0;
+
+    for i in 0..scale {
+        for j in 0..scale {
+            let pixel = signature_image.get_pixel(i, j);
+
+            let value: f64 = pixel[0].into();
+            bits[cur_value] = value >= mean;
+
+            cur_value += 1;
         }
     }
 
-    return value;
+    bits
 }
 
+/// Computes the `dhash` value of a given image
+///
+/// A `dhash` is a signature of an image that can be compared to other images
+/// 
+/// Requires the `image` crate for loading in the image
+/// 
+/// # Example
+/// 
+/// ```no_run
+/// # use dhash::get_dhash;
+/// # fn main() {
+/// let img = image::open("test.jpg").expect("Could not open image");
+/// let dhash = get_dhash(&img);
+/// # }
+/// ```
+/// 
+pub fn get_dhash<I: GenericImageView + 'static>(img: &I) -> u64 {
+    bits_to_u64(&gradient_bits(img, IMG_SCALE))
+}
+
+/// A grayscale signature image produced for an image whose pixel type is `I`
+type GreySignature<I> = ImageBuffer<
+    Luma<<<I as GenericImageView>::Pixel as Pixel>::Subpixel>,
+    std::vec::Vec<<<I as GenericImageView>::Pixel as Pixel>::Subpixel>,
+>;
+
 /// Converts the image to a `dhash` image
 ///
 /// Returns an image that is a 9x8 grayscale image so the pixels can be used in comparison
@@ -109,17 +357,15 @@ pub fn get_dhash<I: GenericImageView + 'static>(img: &I) -> u64 {
 /// let grey_signature_image = to_grey_signature_image(&img);
 /// # }
 /// ```
-pub fn to_grey_signature_image<I: GenericImageView + 'static>(
-    img: &I,
-) -> ImageBuffer<
-    Luma<<<I as GenericImageView>::Pixel as Pixel>::Subpixel>,
-    std::vec::Vec<<<I as GenericImageView>::Pixel as Pixel>::Subpixel>,
-> {
-    let grey_image = grayscale(img);
+pub fn to_grey_signature_image<I: GenericImageView + 'static>(img: &I) -> GreySignature<I> {
+    grey_signature_sized(img, IMG_SCALE)
+}
 
-    let signature_image = resize(&grey_image, IMG_SCALE + 1, IMG_SCALE, FilterType::Nearest);
+/// Builds a `(scale + 1) x scale` grayscale signature image for the gradient hash
+fn grey_signature_sized<I: GenericImageView + 'static>(img: &I, scale: u32) -> GreySignature<I> {
+    let grey_image = grayscale(img);
 
-    return signature_image;
+    resize(&grey_image, scale + 1, scale, FilterType::Nearest)
 }
 
 /// Returns the Hamming Distance between two `dhashes`
@@ -137,3 +383,395 @@ pub fn to_grey_signature_image<I: GenericImageView + 'static>(
 pub fn hamming_distance(left: u64, right: u64) -> u32 {
     (left ^ right).count_ones()
 }
+
+/// A perceptual hash of arbitrary length, backed by a packed bit buffer
+///
+/// Unlike the bare `u64` returned by [`get_dhash`] and [`hash_image`], a `Hash`
+/// can hold hashes larger than 64 bits (e.g. a 16x16 grid produces 256 bits).
+/// Hashes are compared with [`Hash::hamming_distance`], which works across any
+/// matching length by XORing the byte buffers and counting set bits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hash {
+    bytes: Vec<u8>,
+    bits: usize,
+}
+
+impl Hash {
+    /// Packs a slice of bits into a `Hash`, least significant bit first
+    fn from_bits(input: &[bool]) -> Hash {
+        let mut bytes = vec![0u8; input.len().div_ceil(8)];
+
+        for (i, bit) in input.iter().enumerate() {
+            if *bit {
+                bytes[i / 8] |= 1 << (i % 8);
+            }
+        }
+
+        Hash {
+            bytes,
+            bits: input.len(),
+        }
+    }
+
+    /// The number of bits in this hash
+    pub fn bit_count(&self) -> usize {
+        self.bits
+    }
+
+    /// The raw bytes backing this hash
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the hash as a `u64`, valid for the 8x8 (64 bit) case
+    ///
+    /// Bits beyond the first 64 are ignored and missing bits are treated as
+    /// zero, so this matches the value returned by [`get_dhash`].
+    ///
+    /// # Example
+    /// ```
+    /// # use dhash::{hash_image, hash_image_sized, HashAlg};
+    /// use image::{ImageBuffer, Luma};
+    /// let img = ImageBuffer::from_fn(64, 64, |x, y| Luma([((x ^ y) % 256) as u8]));
+    /// for alg in [HashAlg::Gradient, HashAlg::Mean, HashAlg::Dct] {
+    ///     assert_eq!(hash_image_sized(&img, alg, 8).to_u64(), hash_image(&img, alg));
+    /// }
+    /// ```
+    pub fn to_u64(&self) -> u64 {
+        let mut value = 0u64;
+
+        for (i, byte) in self.bytes.iter().take(8).enumerate() {
+            value |= u64::from(*byte) << (i * 8);
+        }
+
+        value
+    }
+
+    /// Returns the Hamming Distance between two hashes of equal length
+    ///
+    /// The closer this number is to 0, the more similar the images are, with 0
+    /// being an exact match.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the two hashes have a different number of bits.
+    ///
+    /// # Example
+    /// ```
+    /// # use dhash::{hash_image_sized, hamming_distance, HashAlg};
+    /// use image::{ImageBuffer, Luma};
+    /// let a = ImageBuffer::from_fn(64, 64, |x, y| Luma([((x + 2 * y) % 256) as u8]));
+    /// let b = ImageBuffer::from_fn(64, 64, |x, y| Luma([((3 * x + y) % 256) as u8]));
+    /// let ha = hash_image_sized(&a, HashAlg::Gradient, 8);
+    /// let hb = hash_image_sized(&b, HashAlg::Gradient, 8);
+    /// // The byte-buffer distance matches the legacy u64 implementation at 64 bits.
+    /// assert_eq!(ha.hamming_distance(&hb), hamming_distance(ha.to_u64(), hb.to_u64()));
+    /// ```
+    pub fn hamming_distance(&self, other: &Hash) -> u32 {
+        assert_eq!(
+            self.bits, other.bits,
+            "cannot compare hashes of different lengths"
+        );
+
+        self.bytes
+            .iter()
+            .zip(other.bytes.iter())
+            .map(|(left, right)| (left ^ right).count_ones())
+            .sum()
+    }
+
+    /// Returns a `0.0`–`1.0` similarity score derived from the Hamming distance
+    ///
+    /// This is simply `1.0 - distance / bits`, so `1.0` is an exact match and
+    /// `0.0` means every bit differs.  It offers a perception-like percentage
+    /// without the full-reference cost of [`ssim`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the two hashes have a different number of bits.
+    pub fn similarity(&self, other: &Hash) -> f64 {
+        if self.bits == 0 && other.bits == 0 {
+            return 1.0;
+        }
+
+        1.0 - f64::from(self.hamming_distance(other)) / self.bits as f64
+    }
+
+    /// Builds a `Hash` directly from its raw bytes, treating every bit as used
+    fn from_bytes(bytes: Vec<u8>) -> Hash {
+        let bits = bytes.len() * 8;
+        Hash { bytes, bits }
+    }
+
+    /// Encodes the hash as a standard (padded) base64 string
+    ///
+    /// # Example
+    /// ```
+    /// # use dhash::Hash;
+    /// let h = Hash::from_hex("deadbeef").unwrap();
+    /// // Known vector plus a base64 round-trip.
+    /// assert_eq!(h.to_base64(), "3q2+7w==");
+    /// assert_eq!(Hash::from_base64(&h.to_base64()).unwrap(), h);
+    /// ```
+    pub fn to_base64(&self) -> String {
+        base64_encode(&self.bytes)
+    }
+
+    /// Encodes the hash as a lowercase hex string
+    ///
+    /// # Example
+    /// ```
+    /// # use dhash::Hash;
+    /// let h = Hash::from_base64("3q2+7w==").unwrap();
+    /// assert_eq!(h.to_hex(), "deadbeef");
+    /// assert_eq!(Hash::from_hex(&h.to_hex()).unwrap(), h);
+    /// ```
+    pub fn to_hex(&self) -> String {
+        let mut out = String::with_capacity(self.bytes.len() * 2);
+        for byte in &self.bytes {
+            out.push_str(&format!("{:02x}", byte));
+        }
+        out
+    }
+
+    /// Parses a hash from a standard (padded) base64 string
+    ///
+    /// # Example
+    /// ```
+    /// # use dhash::Hash;
+    /// assert!(Hash::from_base64("3q2+7w==").is_ok());
+    /// assert!(Hash::from_base64("*invalid*").is_err());
+    /// ```
+    pub fn from_base64(input: &str) -> Result<Hash, ParseHashError> {
+        Ok(Hash::from_bytes(base64_decode(input)?))
+    }
+
+    /// Parses a hash from a lowercase or uppercase hex string
+    ///
+    /// # Example
+    /// ```
+    /// # use dhash::Hash;
+    /// assert!(Hash::from_hex("abc").is_err()); // odd length
+    /// assert!(Hash::from_hex("zz").is_err()); // non-hex digit
+    /// let h = Hash::from_hex("00ff").unwrap();
+    /// assert_eq!(h.to_hex(), "00ff");
+    /// ```
+    pub fn from_hex(input: &str) -> Result<Hash, ParseHashError> {
+        if !input.len().is_multiple_of(2) {
+            return Err(ParseHashError);
+        }
+
+        let mut bytes = Vec::with_capacity(input.len() / 2);
+
+        for chunk in input.as_bytes().chunks(2) {
+            let hi = hex_digit(chunk[0])?;
+            let lo = hex_digit(chunk[1])?;
+            bytes.push(hi << 4 | lo);
+        }
+
+        Ok(Hash::from_bytes(bytes))
+    }
+}
+
+impl std::fmt::Display for Hash {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.to_base64())
+    }
+}
+
+impl std::str::FromStr for Hash {
+    type Err = ParseHashError;
+
+    fn from_str(s: &str) -> Result<Hash, ParseHashError> {
+        Hash::from_base64(s)
+    }
+}
+
+/// Error returned when a hash string cannot be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseHashError;
+
+impl std::fmt::Display for ParseHashError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "invalid hash string")
+    }
+}
+
+impl std::error::Error for ParseHashError {}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(input: &[u8]) -> String {
+    let mut out = String::with_capacity(input.len().div_ceil(3) * 4);
+
+    for chunk in input.chunks(3) {
+        let b0 = chunk[0] as usize;
+        let b1 = chunk.get(1).copied().unwrap_or(0) as usize;
+        let b2 = chunk.get(2).copied().unwrap_or(0) as usize;
+
+        out.push(BASE64_ALPHABET[b0 >> 2] as char);
+        out.push(BASE64_ALPHABET[(b0 & 0x03) << 4 | b1 >> 4] as char);
+
+        if chunk.len() > 1 {
+            out.push(BASE64_ALPHABET[(b1 & 0x0f) << 2 | b2 >> 6] as char);
+        } else {
+            out.push('=');
+        }
+
+        if chunk.len() > 2 {
+            out.push(BASE64_ALPHABET[b2 & 0x3f] as char);
+        } else {
+            out.push('=');
+        }
+    }
+
+    out
+}
+
+fn base64_value(byte: u8) -> Result<usize, ParseHashError> {
+    BASE64_ALPHABET
+        .iter()
+        .position(|candidate| *candidate == byte)
+        .ok_or(ParseHashError)
+}
+
+fn base64_decode(input: &str) -> Result<Vec<u8>, ParseHashError> {
+    let trimmed = input.trim_end_matches('=');
+    let bytes = trimmed.as_bytes();
+
+    let mut out = Vec::with_capacity(trimmed.len() / 4 * 3);
+
+    for chunk in bytes.chunks(4) {
+        if chunk.len() < 2 {
+            return Err(ParseHashError);
+        }
+
+        let v0 = base64_value(chunk[0])?;
+        let v1 = base64_value(chunk[1])?;
+
+        out.push((v0 << 2 | v1 >> 4) as u8);
+
+        if chunk.len() > 2 {
+            let v2 = base64_value(chunk[2])?;
+            out.push((v1 << 4 | v2 >> 2) as u8);
+
+            if chunk.len() > 3 {
+                let v3 = base64_value(chunk[3])?;
+                out.push((v2 << 6 | v3) as u8);
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+fn hex_digit(byte: u8) -> Result<u8, ParseHashError> {
+    match byte {
+        b'0'..=b'9' => Ok(byte - b'0'),
+        b'a'..=b'f' => Ok(byte - b'a' + 10),
+        b'A'..=b'F' => Ok(byte - b'A' + 10),
+        _ => Err(ParseHashError),
+    }
+}
+
+/// Side length of the non-overlapping block used by [`ssim`]
+const SSIM_WINDOW: u32 = 8;
+
+/// Computes the Mean Structural Similarity (MSSIM) between two images
+///
+/// Unlike the hash based scores this is a full-reference comparison: both
+/// images are converted to grayscale and, if necessary, the second is resized
+/// to match the first, then a grid of non-overlapping 8x8 blocks is walked
+/// across them.  This is a block-based approximation of MSSIM (the blocks do
+/// not overlap pixel-by-pixel).  For each block the local means, variances and
+/// covariance feed the SSIM formula with the usual stabilisers
+/// `C1 = (0.01 * L)^2` and `C2 = (0.03 * L)^2` for a dynamic range `L` of 255;
+/// the block scores are averaged to give a single figure of at most `1.0`,
+/// where `1.0` is identical.  Blocks whose contents are anti-correlated score
+/// below `0.0`, so the average can be negative.
+///
+/// # Example
+/// ```
+/// # use dhash::ssim;
+/// use image::{ImageBuffer, Luma};
+/// let img = ImageBuffer::from_fn(32, 32, |x, y| Luma([((x * 5 + y * 3) % 256) as u8]));
+/// // An image is perfectly similar to itself.
+/// assert_eq!(ssim(&img, &img), 1.0);
+/// // A degraded copy scores strictly lower.
+/// let degraded =
+///     ImageBuffer::from_fn(32, 32, |x, y| Luma([(((x * 5 + y * 3) % 256) as u8) ^ 0x55]));
+/// assert!(ssim(&img, &degraded) < 1.0);
+/// ```
+pub fn ssim<I, J>(img1: &I, img2: &J) -> f64
+where
+    I: GenericImageView + 'static,
+    J: GenericImageView + 'static,
+    <<I as GenericImageView>::Pixel as Pixel>::Subpixel: Into<f64>,
+    <<J as GenericImageView>::Pixel as Pixel>::Subpixel: Into<f64>,
+{
+    let left = grayscale(img1);
+
+    let (width, height) = left.dimensions();
+
+    // Resize the second image to the first so the windows line up.
+    let right = resize(&grayscale(img2), width, height, FilterType::Triangle);
+
+    const L: f64 = 255.0;
+    let c1 = (0.01 * L).powi(2);
+    let c2 = (0.03 * L).powi(2);
+
+    let mut total = 0.0;
+    let mut windows = 0;
+
+    let mut y = 0;
+    while y + SSIM_WINDOW <= height {
+        let mut x = 0;
+        while x + SSIM_WINDOW <= width {
+            let count = f64::from(SSIM_WINDOW * SSIM_WINDOW);
+
+            let mut sum_x = 0.0;
+            let mut sum_y = 0.0;
+            let mut sum_xx = 0.0;
+            let mut sum_yy = 0.0;
+            let mut sum_xy = 0.0;
+
+            for j in 0..SSIM_WINDOW {
+                for i in 0..SSIM_WINDOW {
+                    let lx: f64 = left.get_pixel(x + i, y + j)[0].into();
+                    let ly: f64 = right.get_pixel(x + i, y + j)[0].into();
+
+                    sum_x += lx;
+                    sum_y += ly;
+                    sum_xx += lx * lx;
+                    sum_yy += ly * ly;
+                    sum_xy += lx * ly;
+                }
+            }
+
+            let mean_x = sum_x / count;
+            let mean_y = sum_y / count;
+
+            let var_x = sum_xx / count - mean_x * mean_x;
+            let var_y = sum_yy / count - mean_y * mean_y;
+            let cov_xy = sum_xy / count - mean_x * mean_y;
+
+            let numerator = (2.0 * mean_x * mean_y + c1) * (2.0 * cov_xy + c2);
+            let denominator =
+                (mean_x * mean_x + mean_y * mean_y + c1) * (var_x + var_y + c2);
+
+            total += numerator / denominator;
+            windows += 1;
+
+            x += SSIM_WINDOW;
+        }
+
+        y += SSIM_WINDOW;
+    }
+
+    if windows == 0 {
+        return 1.0;
+    }
+
+    total / f64::from(windows)
+}
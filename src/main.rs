@@ -1,25 +1,86 @@
-mod lib;
-
-use crate::lib::{get_dhash, hamming_distance};
+use dhash::bktree::scan_directory;
+use dhash::{hash_image_sized, ssim, Hash, HashAlg};
 
 use ::structopt::StructOpt;
+use structopt::clap::AppSettings;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use std::env;
 
+fn parse_alg(src: &str) -> Result<HashAlg, String> {
+    match src.to_lowercase().as_str() {
+        "gradient" | "dhash" => Ok(HashAlg::Gradient),
+        "mean" | "ahash" => Ok(HashAlg::Mean),
+        "dct" | "phash" => Ok(HashAlg::Dct),
+        other => Err(format!("unknown algorithm `{}`", other)),
+    }
+}
+
 #[derive(Debug, StructOpt)]
-#[structopt(name = "dhash", about = "dhash image generator")]
+#[structopt(
+    name = "dhash",
+    about = "dhash image generator",
+    setting = AppSettings::ArgsNegateSubcommands,
+    setting = AppSettings::SubcommandsNegateReqs
+)]
 struct ConfigContext {
-    #[structopt(parse(from_os_str))]
-    input: PathBuf,
+    #[structopt(subcommand)]
+    cmd: Option<Command>,
 
     #[structopt(parse(from_os_str))]
-    compare: Option<PathBuf>,
+    input: Option<PathBuf>,
+
+    /// A second image path, or a precomputed base64 hash, to compare against
+    compare: Option<String>,
+
+    /// Hashing algorithm to use: `gradient` (dhash), `mean` (ahash) or `dct` (phash)
+    #[structopt(long = "alg", default_value = "gradient", parse(try_from_str = parse_alg))]
+    alg: HashAlg,
+
+    /// Print the compact base64 hash form instead of the decimal value
+    #[structopt(long = "base64")]
+    base64: bool,
+
+    /// Compare the two images with structural similarity (MSSIM) instead of hash distance
+    #[structopt(long = "ssim")]
+    ssim: bool,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Scan a directory and group near-duplicate images together
+    Scan {
+        #[structopt(parse(from_os_str))]
+        dir: PathBuf,
+
+        /// Maximum Hamming distance for two images to count as near-duplicates
+        #[structopt(long = "threshold", default_value = "10")]
+        threshold: u32,
+
+        /// Hashing algorithm to use: `gradient` (dhash), `mean` (ahash) or `dct` (phash)
+        #[structopt(long = "alg", default_value = "gradient", parse(try_from_str = parse_alg))]
+        alg: HashAlg,
+
+        /// Directory to cache computed hashes in (requires the `cache` feature)
+        #[structopt(long = "cache-dir", parse(from_os_str))]
+        cache_dir: Option<PathBuf>,
+    },
+}
+
+impl ConfigContext {
+    /// Formats a hash for output, honouring the `--base64` flag
+    fn format(&self, hash: &Hash) -> String {
+        if self.base64 {
+            hash.to_base64()
+        } else {
+            hash.to_u64().to_string()
+        }
+    }
 }
 
 fn main() {
-    if let Err(_) = env::var("RUST_LOG") {
+    if env::var("RUST_LOG").is_err() {
         env::set_var("RUST_LOG", "dhash=DEBUG");
     }
 
@@ -27,22 +88,85 @@ fn main() {
 
     let context = ConfigContext::from_args();
 
-    let img = image::open(&context.input).expect("Could not open image");
+    if let Some(Command::Scan {
+        dir,
+        threshold,
+        alg,
+        cache_dir,
+    }) = &context.cmd
+    {
+        run_scan(dir, *alg, *threshold, cache_dir.as_deref());
+        return;
+    }
 
-    let input_dhash = get_dhash(&img);
+    let input = context
+        .input
+        .as_ref()
+        .expect("An input image is required");
 
-    println!("dhash for {} is `{}`", context.input.display(), input_dhash);
+    if context.ssim {
+        let compare = context
+            .compare
+            .as_ref()
+            .expect("A second image is required for --ssim");
 
-    if let Some(compare) = context.compare {
-        let compare_img = image::open(&compare).expect("Could not open compare image");
+        let img = image::open(input).expect("Could not open image");
+        let compare_img = image::open(compare).expect("Could not open compare image");
 
-        let compare_dhash = get_dhash(&compare_img);
+        println!("ssim is: {}", ssim(&img, &compare_img));
+        return;
+    }
+
+    let img = image::open(input).expect("Could not open image");
+
+    let input_dhash = hash_image_sized(&img, context.alg, 8);
+
+    println!(
+        "dhash for {} is `{}`",
+        input.display(),
+        context.format(&input_dhash)
+    );
 
-        println!("dhash for {} is `{}`", compare.display(), compare_dhash);
+    if let Some(compare) = &context.compare {
+        // A compare argument that points at an existing file is treated as an
+        // image to hash, otherwise it is parsed as a precomputed hash string.
+        let compare_dhash = if Path::new(compare).is_file() {
+            let compare_img = image::open(compare).expect("Could not open compare image");
+
+            let compare_dhash = hash_image_sized(&compare_img, context.alg, 8);
+
+            println!("dhash for {} is `{}`", compare, context.format(&compare_dhash));
+
+            compare_dhash
+        } else {
+            Hash::from_base64(compare).expect("Compare argument is neither a file nor a valid base64 hash")
+        };
 
         println!(
             "distance is: {}",
-            hamming_distance(input_dhash, compare_dhash)
+            input_dhash.hamming_distance(&compare_dhash)
         );
     }
 }
+
+/// Runs the `scan` subcommand, printing each group of near-duplicate images
+fn run_scan(dir: &Path, alg: HashAlg, threshold: u32, cache_dir: Option<&Path>) {
+    let clusters =
+        scan_directory(dir, alg, threshold, cache_dir).expect("Could not read directory");
+
+    let mut group = 1;
+
+    for cluster in clusters {
+        if cluster.len() < 2 {
+            continue;
+        }
+
+        println!("group {}:", group);
+
+        for path in cluster {
+            println!("  {}", path.display());
+        }
+
+        group += 1;
+    }
+}